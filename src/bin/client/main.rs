@@ -11,22 +11,30 @@ extern crate getopts;
 use getopts::Options;
 
 // lib.rs
-use rust_filestore::{Operation, Request};
-use rust_filestore::{serialize_request, deserialize_response};
+use rust_filestore::{Operation, RequestHeader};
+use rust_filestore::{perform_handshake, perform_auth_client, write_request_header, read_response_header, write_body_stream, read_body_stream_hashed, hash_reader};
+use rust_filestore::AsyncStream;
 
-use std::io;
 use std::env;
+use std::fs::File as StdFile;
+use std::io::BufReader;
 use std::path::Path;
 use std::error::Error;
+use std::sync::Arc;
 
 use tokio::net::TcpStream;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, SeekFrom};
 use tokio::fs::File;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
 
 struct Config {
     addr: String, // default is 127.0.0.0:8080
     filename: Option<String>, // path to file
-    operation: Operation // what to do
+    operation: Operation, // what to do
+    access_key: String, // pre-shared key the server requires
+    tls_ca: Option<String> // path to a PEM root CA, when TLS is enabled
 }
 
 impl Config {
@@ -49,12 +57,16 @@ fn parse_args(args: Vec<String>) -> Result<Config, Box<dyn Error>> {
 
     let mut opts = Options::new();
     opts.optopt("a", "addr", "server address and port", "<ip>"); // 127.0.0.1:8080 by default
-    
+    opts.optopt("k", "key", "pre-shared access key required by the server", "<key>");
+    opts.optflag("", "tls", "connect over TLS");
+    opts.optopt("", "ca", "PEM root CA to validate the server's certificate", "<path>");
+
     // operations: exactly one of {r, w, d} is required
     opts.optflagopt("r", "read", "read from server", "<file>");
     opts.optflagopt("w", "write", "write file to server", "<file>");
     opts.optflagopt("d", "delete", "delete file on server", "<file>");
     opts.optflag("l", "list", "list all files on server");
+    opts.optflagopt("e", "exists", "check whether a file exists on server", "<file>");
 
     // help option
     opts.optflag("h", "help", "print this help menu");
@@ -71,9 +83,9 @@ fn parse_args(args: Vec<String>) -> Result<Config, Box<dyn Error>> {
     }
 
     // ensure that exactly one operation is provided
-    let options = vec!["r", "w", "d", "l"];
+    let options = vec!["r", "w", "d", "l", "e"];
     if options.iter().filter(|&&opt| matches.opt_present(opt)).count() != 1 {
-        return Err("Select exactly one of {-r, -w, -d, -l}".into());
+        return Err("Select exactly one of {-r, -w, -d, -l, -e}".into());
     }
 
     // ip and port
@@ -110,70 +122,101 @@ fn parse_args(args: Vec<String>) -> Result<Config, Box<dyn Error>> {
         }
     }
 
-    return Ok(Config {addr, filename, operation});
+    let access_key = matches.opt_str("k").unwrap_or_default();
+
+    let tls_ca = if matches.opt_present("tls") {
+        Some(matches.opt_str("ca").ok_or("--tls requires --ca <path>")?)
+    } else {
+        None
+    };
+
+    return Ok(Config {addr, filename, operation, access_key, tls_ca});
+}
+
+// loads a PEM root CA from `path` into a fresh RootCertStore
+fn load_root_ca(path: &str) -> Result<RootCertStore, Box<dyn Error>> {
+    let file = StdFile::open(path)?;
+    let mut reader = BufReader::new(file);
+    let certs: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+
+    let mut store = RootCertStore::empty();
+    for cert in certs {
+        store.add(cert)?;
+    }
+
+    Ok(store)
+}
+
+// connects to `config.addr`, wrapping the stream in TLS when configured
+async fn connect(config: &Config) -> Result<Box<dyn AsyncStream>, Box<dyn Error>> {
+    let tcp_stream = TcpStream::connect(&config.addr).await?;
+
+    match &config.tls_ca {
+        Some(ca_path) => {
+            let root_store = load_root_ca(ca_path)?;
+            let tls_config = ClientConfig::builder()
+                .with_root_certificates(root_store)
+                .with_no_client_auth();
+            let connector = TlsConnector::from(Arc::new(tls_config));
+
+            let host = config.addr.split(':').next().unwrap_or(&config.addr).to_string();
+            let domain = ServerName::try_from(host)?;
+
+            Ok(Box::new(connector.connect(domain, tcp_stream).await?))
+        }
+        None => Ok(Box::new(tcp_stream))
+    }
 }
 
 // send file and operation to server
 async fn run(config: &Config) -> Result<(), Box<dyn Error>> {
-    // package arguments into a request
-    let mut filebytes = vec![];
     let filename = config.filename.as_deref().unwrap_or("");
     let basename = Path::new(filename).file_name().unwrap_or_default().to_string_lossy().to_string();
 
-    if config.operation == Operation::WRITE && !filename.is_empty() {
-        if let Ok(mut f) = File::open(filename).await {
-            f.read_to_end(&mut filebytes).await?;
-        } else {
-            return Err("File not found".into());
+    // open the source file up front for WRITE so a missing file fails fast
+    let mut source_file = if config.operation == Operation::WRITE && !filename.is_empty() {
+        match File::open(filename).await {
+            Ok(f) => Some(f),
+            Err(_) => return Err("File not found".into())
         }
-    }
-    
-    let req = Request {
+    } else {
+        None
+    };
+
+    // checksum the file before streaming it, then rewind to the start
+    let digest = if let Some(file) = source_file.as_mut() {
+        let digest = hash_reader(file).await?;
+        file.seek(SeekFrom::Start(0)).await?;
+        Some(digest)
+    } else {
+        None
+    };
+
+    // establish connection with server, over TLS if configured
+    let mut stream = connect(config).await?;
+
+    // derive a per-connection AES-256-GCM key via X25519 key exchange
+    let cipher = perform_handshake(&mut stream).await?;
+
+    // authenticate with the server's pre-shared access key
+    perform_auth_client(&mut stream, &cipher, &config.access_key).await?;
+
+    // send op + filename header, then stream the file body for WRITE
+    write_request_header(&mut stream, &cipher, &RequestHeader {
         op: config.operation,
         filename: basename,
-        filebytes
-    };
+        digest
+    }).await?;
 
-    let request_buffer = serialize_request(&req).await?;
-    
-    // establish connection with server
-    let stream = TcpStream::connect(&config.addr).await?;
-
-    // wait for the socket to be writable
-    stream.writable().await?;
-    
-    // loop until write to server is successful
-    loop {
-        match stream.try_write(&request_buffer) {
-            Ok(_) => break,
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
-            Err(e) => return Err(e.into())
-        }
+    if let Some(file) = source_file.as_mut() {
+        write_body_stream(file, &mut stream, &cipher, None).await?;
     }
 
-    // wait until server is readable
-    stream.readable().await?;
-    
-    let mut response_buffer = vec![0; 1<<20]; // about 1MB
-
-    // loop until stream is read into buffer successfully
-    loop {
-        match stream.try_read(&mut response_buffer) {
-            Ok(n) => {
-                response_buffer.truncate(n);
-                break;
-            },
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue, // blocking error; try again
-            Err(e) => return Err(e.into()) // panic if any other error
-        }
-    }
-    
-    // receive and deserialize server response
-    let response = deserialize_response(&response_buffer).await?;
-    
-    // write file to disk if there is one
-    if let (Some(filename), Some(filebytes)) = (&response.filename, &response.filebytes) {
-        if config.operation == Operation::READ {
+    // receive the response header, then the file body for READ
+    let response = read_response_header(&mut stream, &cipher).await?;
+
+    if config.operation == Operation::READ && response.ok {
+        if let Some(filename) = &response.filename {
             let path = format!("received/{}", filename);
 
             // create the directory if it doesn't exist
@@ -181,14 +224,23 @@ async fn run(config: &Config) -> Result<(), Box<dyn Error>> {
                 tokio::fs::create_dir_all(parent).await?;
             }
 
-            // write file to disk
             let mut file = File::create(&path).await?;
-            file.write_all(filebytes).await?;
+            let received_digest = read_body_stream_hashed(&mut stream, &mut file, &cipher, None).await?;
             file.flush().await?;
+
+            if response.digest.is_some_and(|expected| expected != received_digest) {
+                eprintln!("Warning: checksum mismatch for '{}'; file may be corrupted.", filename);
+                return Err("Checksum verification failed".into());
+            }
+
             println!("File '{}' saved successfully.", filename);
         }
     }
 
+    if config.operation == Operation::EXISTS && response.ok {
+        println!("size = {} bytes, modified = {} (unix time)", response.size.unwrap_or(0), response.modified.unwrap_or(0));
+    }
+
     if response.ok {
         println!("{}", &response.msg);
         Ok(())
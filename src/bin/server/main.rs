@@ -7,95 +7,253 @@
 //! deletes a file of their choice
 //!
 
+extern crate getopts;
+use getopts::Options;
+
 // lib.rs
-use rust_filestore::{Operation, Response};
-use rust_filestore::{deserialize_request, serialize_response};
+use rust_filestore::{Operation, ErrorKind, ResponseHeader};
+use rust_filestore::{perform_handshake, perform_auth_server, generate_access_key};
+use rust_filestore::{read_request_header, write_response_header, read_body_stream_hashed, write_body_stream, hash_reader};
+use rust_filestore::AsyncStream;
 
-use std::io;
+use std::env;
 use std::error::Error;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::fs::File as StdFile;
+use std::future::Future;
+use std::io::BufReader;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, SeekFrom};
 use tokio::fs::File;
+use tokio::time::timeout;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
 
-async fn handle_client(stream: TcpStream) -> Result<(), Box<dyn Error>> {
-    // wait until client is readable
-    stream.readable().await?;
-    
-    // max buffer size = 1.048576 MB
-    let mut request_buffer = vec![0; 1<<20];
+// how long a connection may sit idle on a single phase (handshake, auth,
+// request header, or response header) before it's dropped
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
 
-    // loop until read from stream reads successfully
-    loop {
-        match stream.try_read(&mut request_buffer) {
-            Ok(n) => {
-                request_buffer.truncate(n); // excess space
-                break;
-            },
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue, // blocking error; try again
-            Err(e) => return Err(e.into()) // panic on any other error
+struct Config {
+    addr: String, // default is 127.0.0.1:8080
+    access_key: String, // pre-shared key clients must present before any operation
+    tls: Option<(String, String)>, // (cert path, private key path), when TLS is enabled
+    timeout: Duration // max idle time allowed per connection phase
+}
+
+// races `fut` against `duration`, turning an expiry into a plain error
+// so a stalled client can't pin a task open forever
+async fn with_timeout<T>(duration: Duration, fut: impl Future<Output = Result<T, Box<dyn Error>>>) -> Result<T, Box<dyn Error>> {
+    match timeout(duration, fut).await {
+        Ok(result) => result,
+        Err(_) => Err("Connection timed out waiting for client".into())
+    }
+}
+
+fn usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} [-a addr] [-k key] [--tls-cert cert.pem --tls-key key.pem] [--timeout secs]", program);
+    print!("{}", opts.usage(&brief));
+}
+
+fn parse_args(args: Vec<String>) -> Result<Config, Box<dyn Error>> {
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optopt("a", "addr", "address and port to listen on", "<ip:port>"); // 127.0.0.1:8080 by default
+    opts.optopt("k", "key", "pre-shared access key clients must present", "<key>"); // random by default
+    opts.optopt("", "tls-cert", "PEM certificate chain for TLS", "<path>");
+    opts.optopt("", "tls-key", "PEM private key for TLS", "<path>");
+    opts.optopt("", "timeout", "seconds a connection may idle before being dropped", "<secs>");
+    opts.optflag("h", "help", "print this help menu");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(e) => return Err(e.into())
+    };
+
+    if matches.opt_present("h") {
+        usage(&program, opts);
+        return Err("Help menu".into());
+    }
+
+    let addr = matches.opt_str("a").unwrap_or_else(|| String::from("127.0.0.1:8080"));
+
+    let access_key = match matches.opt_str("k") {
+        Some(key) => key,
+        None => {
+            let key = generate_access_key();
+            println!("No access key given, generated one: {}", key);
+            key
         }
+    };
+
+    let tls = match (matches.opt_str("tls-cert"), matches.opt_str("tls-key")) {
+        (Some(cert), Some(key)) => Some((cert, key)),
+        (None, None) => None,
+        _ => return Err("--tls-cert and --tls-key must be given together".into())
+    };
+
+    let timeout = match matches.opt_str("timeout") {
+        Some(secs) => Duration::from_secs(secs.parse()?),
+        None => Duration::from_secs(DEFAULT_TIMEOUT_SECS)
+    };
+
+    Ok(Config { addr, access_key, tls, timeout })
+}
+
+// loads a PEM certificate chain from `path`
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, Box<dyn Error>> {
+    let file = StdFile::open(path)?;
+    let mut reader = BufReader::new(file);
+    Ok(rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?)
+}
+
+// loads a single PEM private key from `path`
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>, Box<dyn Error>> {
+    let file = StdFile::open(path)?;
+    let mut reader = BufReader::new(file);
+    rustls_pemfile::private_key(&mut reader)?.ok_or_else(|| "No private key found in file".into())
+}
+
+fn build_tls_acceptor(cert_path: &str, key_path: &str) -> Result<TlsAcceptor, Box<dyn Error>> {
+    let certs = load_certs(cert_path)?;
+    let key = load_key(key_path)?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+async fn handle_client(mut stream: Box<dyn AsyncStream>, access_key: Arc<String>, idle_timeout: Duration) -> Result<(), Box<dyn Error>> {
+    // derive a per-connection AES-256-GCM key via X25519 key exchange
+    let cipher = with_timeout(idle_timeout, perform_handshake(&mut stream)).await?;
+
+    // authenticate the client's pre-shared access key before honoring any request
+    if !with_timeout(idle_timeout, perform_auth_server(&mut stream, &cipher, &access_key)).await? {
+        with_timeout(idle_timeout, write_response_header(&mut stream, &cipher, &ResponseHeader {
+            ok: false,
+            msg: String::from("Unauthorized: invalid access key."),
+            filename: None,
+            digest: None,
+            kind: ErrorKind::Unauthorized,
+            size: None,
+            modified: None
+        })).await?;
+        return Ok(());
     }
 
-    // handle client's request
-    let req = deserialize_request(&request_buffer).await?;
-    
+    // read op + filename header
+    let req = with_timeout(idle_timeout, read_request_header(&mut stream, &cipher)).await?;
+
     // server-side path to file
     let path = format!("files/{}", &req.filename);
 
-    // response to client to be replaced in following match statement
     let response = match req.op {
-        Operation::READ => { // return file to user if it exists
+        Operation::READ => { // stream file back to client if it exists
             match File::open(&path).await {
                 Ok(mut file) => {
-                    let mut contents = vec![];
-                    file.read_to_end(&mut contents).await?;
+                    // checksum the file, then rewind before streaming it out
+                    let digest = hash_reader(&mut file).await?;
+                    file.seek(SeekFrom::Start(0)).await?;
 
-                    Response {
+                    with_timeout(idle_timeout, write_response_header(&mut stream, &cipher, &ResponseHeader {
                         ok: true,
                         msg: String::from("File successfully returned."),
                         filename: Some(req.filename.clone()),
-                        filebytes: Some(contents)
-                    }
+                        digest: Some(digest),
+                        kind: ErrorKind::None,
+                        size: None,
+                        modified: None
+                    })).await?;
+                    write_body_stream(&mut file, &mut stream, &cipher, Some(idle_timeout)).await?;
+                    return Ok(());
                 }
-                Err(_) => Response {
+                Err(e) => ResponseHeader {
                     ok: false,
                     msg: String::from("File not found on server."),
                     filename: None,
-                    filebytes: None
+                    digest: None,
+                    kind: ErrorKind::from_io_error(&e),
+                    size: None,
+                    modified: None
                 }
             }
         },
-        Operation::WRITE => { // store file on disk
-            // create the directory if it doesn't exist
-            if let Some(parent) = std::path::Path::new(&path).parent() {
-                tokio::fs::create_dir_all(parent).await?;
-            }
+        Operation::WRITE => { // stream file from client straight onto disk
+            let created = async {
+                if let Some(parent) = std::path::Path::new(&path).parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                File::create(&path).await
+            }.await;
 
-            // create file
-            let mut file = File::create(&path).await?;
-            file.write_all(&req.filebytes).await?;
-            file.flush().await?;
+            match created {
+                Ok(mut file) => {
+                    let digest = read_body_stream_hashed(&mut stream, &mut file, &cipher, Some(idle_timeout)).await?;
+                    file.flush().await?;
 
-            Response {
-                ok: true,
-                msg: String::from("File successfully stored."),
-                filename: None,
-                filebytes: None
+                    if req.digest.is_some_and(|expected| expected != digest) {
+                        tokio::fs::remove_file(&path).await?;
+                        ResponseHeader {
+                            ok: false,
+                            msg: String::from("Checksum mismatch; file rejected."),
+                            filename: None,
+                            digest: None,
+                            kind: ErrorKind::Internal,
+                            size: None,
+                            modified: None
+                        }
+                    } else {
+                        ResponseHeader {
+                            ok: true,
+                            msg: String::from("File successfully stored."),
+                            filename: None,
+                            digest: None,
+                            kind: ErrorKind::None,
+                            size: None,
+                            modified: None
+                        }
+                    }
+                }
+                Err(e) => {
+                    // the client is already streaming the body right behind its
+                    // request header; drain and discard it so the connection
+                    // stays in sync before the error response is sent
+                    read_body_stream_hashed(&mut stream, &mut tokio::io::sink(), &cipher, Some(idle_timeout)).await?;
+                    ResponseHeader {
+                        ok: false,
+                        msg: String::from("File could not be created on server."),
+                        filename: None,
+                        digest: None,
+                        kind: ErrorKind::from_io_error(&e),
+                        size: None,
+                        modified: None
+                    }
+                }
             }
         },
         Operation::DELETE => { // delete file from disk
             match tokio::fs::remove_file(&path).await {
-                Ok(_) => Response {
+                Ok(_) => ResponseHeader {
                         ok: true,
                         msg: String::from("File successfully deleted."),
                         filename: None,
-                        filebytes: None
+                        digest: None,
+                        kind: ErrorKind::None,
+                        size: None,
+                        modified: None
                     },
-                Err(_) => Response {
+                Err(e) => ResponseHeader {
                     ok: false,
                     msg: String::from("File could not be deleted."),
                     filename: None,
-                    filebytes: None
+                    digest: None,
+                    kind: ErrorKind::from_io_error(&e),
+                    size: None,
+                    modified: None
                 }
             }
         },
@@ -103,7 +261,7 @@ async fn handle_client(stream: TcpStream) -> Result<(), Box<dyn Error>> {
             // read directory asynchronously, store filenames in a string
             let mut files = String::new();
             let mut entries = tokio::fs::read_dir("files").await?;
-            
+
             while let Some(entry) = entries.next_entry().await? {
                 let path = entry.path();
                 if path.is_file() {
@@ -112,45 +270,100 @@ async fn handle_client(stream: TcpStream) -> Result<(), Box<dyn Error>> {
                 }
             }
 
-            Response {
+            ResponseHeader {
                 ok: true,
                 msg: files,
                 filename: None,
-                filebytes: None
+                digest: None,
+                kind: ErrorKind::None,
+                size: None,
+                modified: None
+            }
+        },
+        Operation::EXISTS => { // report presence, size, and modification time without reading contents
+            match tokio::fs::metadata(&path).await {
+                Ok(metadata) => {
+                    let modified = metadata.modified().ok()
+                        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                        .map(|d| d.as_secs());
+
+                    ResponseHeader {
+                        ok: true,
+                        msg: String::from("File exists."),
+                        filename: Some(req.filename.clone()),
+                        digest: None,
+                        kind: ErrorKind::None,
+                        size: Some(metadata.len()),
+                        modified
+                    }
+                }
+                Err(e) => ResponseHeader {
+                    ok: false,
+                    msg: String::from("File does not exist on server."),
+                    filename: None,
+                    digest: None,
+                    kind: ErrorKind::from_io_error(&e),
+                    size: None,
+                    modified: None
+                }
             }
         }
     };
 
-    let response_buffer = serialize_response(&response).await?;
-
-    // wait for the socket to be writable
-    stream.writable().await?;
-
-    // loop until response to client is successfully sent
-    loop {
-        match stream.try_write(&response_buffer) {
-            Ok(_) => break,
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue, // readiness event is a false positive
-            Err(e) => return Err(e.into())
-        }
-    }
+    with_timeout(idle_timeout, write_response_header(&mut stream, &cipher, &response)).await?;
 
     Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let addr = "127.0.0.1:8080";
-    let listener = TcpListener::bind(addr).await?;
-    println!("Server listening on {}", addr);
+    let args: Vec<String> = env::args().collect();
+    let config = match parse_args(args) {
+        Ok(cfg) => cfg,
+        Err(ref e) if e.to_string() == String::from("Help menu") => {
+            return Ok(());
+        },
+        Err(e) => return Err(e.into())
+    };
 
-    // handle client connections in infinite loop
+    let listener = TcpListener::bind(&config.addr).await?;
+
+    let tls_acceptor = match &config.tls {
+        Some((cert, key)) => {
+            println!("Server listening on {} (TLS)", config.addr);
+            Some(build_tls_acceptor(cert, key)?)
+        }
+        None => {
+            println!("Server listening on {}", config.addr);
+            None
+        }
+    };
+
+    let access_key = Arc::new(config.access_key);
+    let idle_timeout = config.timeout;
+
+    // handle client connections in infinite loop, one task per connection
     loop {
         match listener.accept().await {
-            Ok((socket, _)) => { // socket is tokio::net::TcpStream, _ is address
-                if let Err(e) = handle_client(socket).await {
-                    return Err(e.into());
-                }
+            Ok((socket, addr)) => { // socket is tokio::net::TcpStream, addr is its peer address
+                let access_key = Arc::clone(&access_key);
+                let tls_acceptor = tls_acceptor.clone();
+                tokio::spawn(async move {
+                    let stream: Box<dyn AsyncStream> = match tls_acceptor {
+                        Some(acceptor) => match acceptor.accept(socket).await {
+                            Ok(tls_stream) => Box::new(tls_stream),
+                            Err(e) => {
+                                eprintln!("TLS handshake with {} failed: {}", addr, e);
+                                return;
+                            }
+                        },
+                        None => Box::new(socket)
+                    };
+
+                    if let Err(e) = handle_client(stream, access_key, idle_timeout).await {
+                        eprintln!("Error handling client {}: {}", addr, e);
+                    }
+                });
             }
             Err(e) => return Err(e.into())
         }
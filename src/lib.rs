@@ -3,11 +3,58 @@
 //! 2024
 //!
 //! Common structs and functions for client and server files
-//! This file defines serialilzation and deserialization
-//! functions for requests and responses
+//! This file defines the framed, streamed wire protocol shared by the
+//! client and server: a fixed header (op + filename) followed by the
+//! payload as a sequence of length-delimited chunks terminated by a
+//! zero-length frame. Streaming chunks means neither side ever has to
+//! hold an entire file in memory, so there's no ceiling on file size.
+//!
+//! Every frame on the wire is encrypted: `perform_handshake` runs an
+//! ephemeral X25519 Diffie-Hellman exchange to derive a shared AES-256-GCM
+//! key, and `write_frame`/`read_frame` use that key to seal/open each
+//! frame so file contents and metadata are never sent in plaintext.
 //!
 
 use std::error::Error;
+use std::future::Future;
+use std::time::Duration;
+
+use aes_gcm::aead::{Aead, KeyInit, OsRng as AeadOsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand_core::RngCore;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::timeout;
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+// a duplex byte stream that can be boxed, letting handle_client/run operate
+// identically over a plain TcpStream or a TLS-wrapped one
+pub trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncStream for T {}
+
+// size of each streamed chunk of file payload
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+// length in bytes of the random nonce prepended to each GCM ciphertext
+const NONCE_LEN: usize = 12;
+
+// length in bytes of the GCM authentication tag appended to each ciphertext
+const GCM_TAG_LEN: usize = 16;
+
+// upper bound on a single frame's on-wire length (nonce + ciphertext). Body
+// frames never exceed CHUNK_SIZE of plaintext; the extra slack covers
+// oversized control frames like LIST's newline-joined filename listing.
+// This keeps a peer-controlled length prefix from forcing a multi-GB
+// allocation before the frame is even decrypted.
+const MAX_FRAME_LEN: usize = NONCE_LEN + CHUNK_SIZE + GCM_TAG_LEN + 1024 * 1024;
+
+// length of the access key made by generate_access_key; perform_auth_*
+// compare keys of any length and do not check it
+pub const ACCESS_KEY_LEN: usize = 8;
+
+// SYN/ACK-style markers used to confirm or reject an access key
+const AUTH_ACCEPTED: u8 = 1;
+const AUTH_REJECTED: u8 = 0;
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Operation {
@@ -15,6 +62,7 @@ pub enum Operation {
     WRITE, // write file to server, as u8 = 1
     DELETE, // delete file from server, as u8 = 2
     LIST, // list all files on server, as u8 = 3
+    EXISTS, // check whether a file is present on server, as u8 = 4
 }
 
 impl Operation {
@@ -24,146 +72,350 @@ impl Operation {
             1 => Some(Operation::WRITE),
             2 => Some(Operation::DELETE),
             3 => Some(Operation::LIST),
+            4 => Some(Operation::EXISTS),
             _ => None,
         }
     }
-    
+
     pub fn to_string(&self) -> &str {
         match self {
             Operation::READ => "READ",
             Operation::WRITE => "WRITE",
             Operation::DELETE => "DELETE",
-            Operation::LIST => "LIST"
+            Operation::LIST => "LIST",
+            Operation::EXISTS => "EXISTS"
+        }
+    }
+}
+
+// machine-usable cause of a failed response, so clients can branch on the
+// cause programmatically rather than parsing `msg`
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    None, // not an error; response succeeded
+    NotFound,
+    PermissionDenied,
+    AlreadyExists,
+    Unauthorized,
+    Internal
+}
+
+impl ErrorKind {
+    pub fn from_u8(value: u8) -> Option<ErrorKind> {
+        match value {
+            0 => Some(ErrorKind::None),
+            1 => Some(ErrorKind::NotFound),
+            2 => Some(ErrorKind::PermissionDenied),
+            3 => Some(ErrorKind::AlreadyExists),
+            4 => Some(ErrorKind::Unauthorized),
+            5 => Some(ErrorKind::Internal),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        *self as u8
+    }
+
+    // maps an io::Error from a filesystem operation onto an ErrorKind
+    pub fn from_io_error(e: &std::io::Error) -> ErrorKind {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+            std::io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+            std::io::ErrorKind::AlreadyExists => ErrorKind::AlreadyExists,
+            _ => ErrorKind::Internal
         }
     }
 }
 
-pub struct Request {
+pub struct RequestHeader {
     pub op: Operation,
     pub filename: String,
-    pub filebytes: Vec<u8>
+    pub digest: Option<[u8; 32]> // SHA-256 of the body being sent, set for WRITE
 }
 
-// Request{op, filename, file} -> Vec[op, len(filename), filename, len(file), file]
-pub async fn serialize_request(req: &Request) -> Result<Vec<u8>, Box<dyn Error>> {
-    let mut result = Vec::new();
+pub struct ResponseHeader {
+    pub ok: bool,
+    pub msg: String,
+    pub filename: Option<String>,
+    pub digest: Option<[u8; 32]>, // SHA-256 of the body being returned, set for READ
+    pub kind: ErrorKind, // ErrorKind::None on success
+    pub size: Option<u64>, // file size in bytes, set for EXISTS
+    pub modified: Option<u64> // last modified time as a unix timestamp, set for EXISTS
+}
+
+// performs an ephemeral X25519 Diffie-Hellman exchange over `stream` and
+// derives an AES-256-GCM cipher from the shared secret (hashed with
+// SHA-256 so the DH output is never used directly as a key)
+pub async fn perform_handshake<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S) -> Result<Aes256Gcm, Box<dyn Error>> {
+    let secret = EphemeralSecret::random();
+    let public = PublicKey::from(&secret);
+
+    stream.write_all(public.as_bytes()).await?;
+
+    let mut peer_bytes = [0u8; 32];
+    stream.read_exact(&mut peer_bytes).await?;
+    let peer_public = PublicKey::from(peer_bytes);
+
+    let shared_secret = secret.diffie_hellman(&peer_public);
+
+    let mut hasher = Sha256::new();
+    hasher.update(shared_secret.as_bytes());
+    let key = hasher.finalize();
 
-    // push op
-    result.push(req.op as u8);
+    Ok(Aes256Gcm::new_from_slice(&key)?)
+}
+
+// generates a random ACCESS_KEY_LEN-character alphanumeric access key
+pub fn generate_access_key() -> String {
+    const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
 
-    // push filename
-    let filename_bytes = req.filename.as_bytes();
-    let filename_len = filename_bytes.len() as u32;
-    result.extend_from_slice(&filename_len.to_be_bytes());
-    result.extend_from_slice(filename_bytes);
+    let mut raw = [0u8; ACCESS_KEY_LEN];
+    AeadOsRng.fill_bytes(&mut raw);
 
-    // push file contents
-    let file_len = req.filebytes.len() as u32;
-    result.extend_from_slice(&file_len.to_be_bytes());
-    result.extend_from_slice(&req.filebytes);
-    
-    Ok(result)
+    raw.iter().map(|b| CHARSET[*b as usize % CHARSET.len()] as char).collect()
 }
 
-// Vec[op, len(filename), filename, len(file), file] -> Request{op, filename, file}
-pub async fn deserialize_request(data: &Vec<u8>) -> Result<Request, Box<dyn Error>> {
-    let mut pos = 0;
+// client side of the pre-shared key handshake: sends `key` and returns an
+// error if the server's confirmation marker indicates rejection
+pub async fn perform_auth_client<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, cipher: &Aes256Gcm, key: &str) -> Result<(), Box<dyn Error>> {
+    write_frame(stream, cipher, key.as_bytes()).await?;
+
+    let ack = read_frame(stream, cipher).await?;
+    if ack.first().copied() != Some(AUTH_ACCEPTED) {
+        return Err("Unauthorized: access key rejected by server".into());
+    }
 
-    // read op
-    let op = Operation::from_u8(data[pos]).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid operation"))?;
-    pos += 1;
+    Ok(())
+}
 
-    // read len(filename)
-    let filename_len = u32::from_be_bytes(data[pos..pos+4].try_into().unwrap()) as usize;
-    pos += 4;
+// server side of the pre-shared key handshake: reads the client's key and
+// replies with a one-byte confirmation marker, returning whether it matched
+pub async fn perform_auth_server<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, cipher: &Aes256Gcm, expected_key: &str) -> Result<bool, Box<dyn Error>> {
+    let key_bytes = read_frame(stream, cipher).await?;
+    let provided = String::from_utf8(key_bytes).unwrap_or_default();
+    let accepted = provided == expected_key;
 
-    // read filename
-    let filename = String::from_utf8(data[pos..pos+filename_len].to_vec()).map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid UTF-8 sequence"))?;
-    pos += filename_len;
+    write_frame(stream, cipher, &[if accepted { AUTH_ACCEPTED } else { AUTH_REJECTED }]).await?;
 
-    // read number of bytes of file
-    let file_len = u32::from_be_bytes(data[pos..pos+4].try_into().unwrap()) as usize;
-    pos += 4;
+    Ok(accepted)
+}
 
-    // read file bytes
-    let filebytes = data[pos..pos+file_len].to_vec();
+// encrypts `data` under `cipher` with a fresh random nonce and writes it
+// as a single length-delimited frame: [len(nonce||ciphertext)][nonce][ciphertext]
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, cipher: &Aes256Gcm, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    AeadOsRng.fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
 
-    Ok(Request{op, filename, filebytes})
+    let ciphertext = cipher.encrypt(nonce, data)
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Encryption failed"))?;
+
+    let len = (NONCE_LEN + ciphertext.len()) as u32;
+    writer.write_all(&len.to_be_bytes()).await?;
+    writer.write_all(&nonce_bytes).await?;
+    writer.write_all(&ciphertext).await?;
+    Ok(())
 }
 
-pub struct Response {
-    pub ok: bool,
-    pub msg: String,
-    pub filename: Option<String>,
-    pub filebytes: Option<Vec<u8>>
+// reads a frame written by write_frame and decrypts it under `cipher`,
+// rejecting the frame if the GCM authentication tag fails to verify
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R, cipher: &Aes256Gcm) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut len_buf = [0u8; 4];
+    reader.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+
+    if len < NONCE_LEN {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Frame too short to contain a nonce").into());
+    }
+
+    if len > MAX_FRAME_LEN {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Frame exceeds maximum allowed length").into());
+    }
+
+    let mut framed = vec![0u8; len];
+    reader.read_exact(&mut framed).await?;
+
+    let (nonce_bytes, ciphertext) = framed.split_at(NONCE_LEN);
+    let nonce = Nonce::from_slice(nonce_bytes);
+
+    cipher.decrypt(nonce, ciphertext)
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "GCM tag verification failed; frame rejected").into())
+}
+
+// RequestHeader{op, filename, digest} -> [op][len(filename)][filename][digest], encrypted frame-by-frame
+// digest is sent as a 32-byte frame when present, or an empty frame otherwise
+pub async fn write_request_header<W: AsyncWrite + Unpin>(writer: &mut W, cipher: &Aes256Gcm, header: &RequestHeader) -> Result<(), Box<dyn Error>> {
+    write_frame(writer, cipher, &[header.op as u8]).await?;
+    write_frame(writer, cipher, header.filename.as_bytes()).await?;
+    write_frame(writer, cipher, header.digest.as_deref().unwrap_or(&[])).await
+}
+
+// [op][len(filename)][filename][digest] -> RequestHeader{op, filename, digest}
+pub async fn read_request_header<R: AsyncRead + Unpin>(reader: &mut R, cipher: &Aes256Gcm) -> Result<RequestHeader, Box<dyn Error>> {
+    let op_bytes = read_frame(reader, cipher).await?;
+    let op = op_bytes.first()
+        .and_then(|b| Operation::from_u8(*b))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid operation"))?;
+
+    let filename_bytes = read_frame(reader, cipher).await?;
+    let filename = String::from_utf8(filename_bytes).map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid UTF-8 sequence"))?;
+
+    let digest = read_digest_frame(reader, cipher).await?;
+
+    Ok(RequestHeader{op, filename, digest})
+}
+
+// ResponseHeader{ok, msg, filename, digest, kind, size, modified} -> each field its own encrypted frame
+pub async fn write_response_header<W: AsyncWrite + Unpin>(writer: &mut W, cipher: &Aes256Gcm, header: &ResponseHeader) -> Result<(), Box<dyn Error>> {
+    write_frame(writer, cipher, &[header.ok as u8]).await?;
+    write_frame(writer, cipher, header.msg.as_bytes()).await?;
+
+    match &header.filename {
+        Some(name) => {
+            write_frame(writer, cipher, &[1u8]).await?;
+            write_frame(writer, cipher, name.as_bytes()).await?;
+        }
+        None => write_frame(writer, cipher, &[0u8]).await?
+    }
+
+    write_frame(writer, cipher, header.digest.as_deref().unwrap_or(&[])).await?;
+    write_frame(writer, cipher, &[header.kind.to_u8()]).await?;
+    write_u64_frame(writer, cipher, header.size).await?;
+    write_u64_frame(writer, cipher, header.modified).await
+}
+
+// writes an optional u64 as a big-endian frame, or an empty frame for None
+async fn write_u64_frame<W: AsyncWrite + Unpin>(writer: &mut W, cipher: &Aes256Gcm, value: Option<u64>) -> Result<(), Box<dyn Error>> {
+    match value {
+        Some(v) => write_frame(writer, cipher, &v.to_be_bytes()).await,
+        None => write_frame(writer, cipher, &[]).await
+    }
 }
 
-// Response{ok, msg, filename, filebytes} -> Vec[ok, len(msg), msg, len(filename), filename, len(filebytes), filebytes]
-pub async fn serialize_response(res: &Response) -> Result<Vec<u8>, Box<dyn Error>> {
-    let mut result = Vec::new();
+// [ok][len(msg)][msg][has_filename][len(filename)][filename][digest][kind][size][modified] -> ResponseHeader
+pub async fn read_response_header<R: AsyncRead + Unpin>(reader: &mut R, cipher: &Aes256Gcm) -> Result<ResponseHeader, Box<dyn Error>> {
+    let ok_bytes = read_frame(reader, cipher).await?;
+    let ok = ok_bytes.first().copied().unwrap_or(0) != 0;
 
-    // push ok
-    result.push(res.ok as u8);
+    let msg_bytes = read_frame(reader, cipher).await?;
+    let msg = String::from_utf8(msg_bytes).map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid UTF-8 sequence"))?;
 
-    // push msg
-    let msg_bytes = res.msg.as_bytes();
-    let msg_len = msg_bytes.len() as u32;
-    result.extend_from_slice(&msg_len.to_be_bytes());
-    result.extend_from_slice(msg_bytes);
+    let has_filename_bytes = read_frame(reader, cipher).await?;
 
-    // push filename
-    if let Some(name) = &res.filename {
-        let filename_bytes = name.as_bytes();
-        let filename_len = filename_bytes.len() as u32;
-        result.extend_from_slice(&filename_len.to_be_bytes());
-        result.extend_from_slice(filename_bytes);
+    let filename = if has_filename_bytes.first().copied().unwrap_or(0) != 0 {
+        let filename_bytes = read_frame(reader, cipher).await?;
+        Some(String::from_utf8(filename_bytes).map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid UTF-8 sequence"))?)
+    } else {
+        None
+    };
+
+    let digest = read_digest_frame(reader, cipher).await?;
+
+    let kind_bytes = read_frame(reader, cipher).await?;
+    let kind = kind_bytes.first()
+        .and_then(|b| ErrorKind::from_u8(*b))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid error kind"))?;
+
+    let size = read_u64_frame(reader, cipher).await?;
+    let modified = read_u64_frame(reader, cipher).await?;
+
+    Ok(ResponseHeader{ok, msg, filename, digest, kind, size, modified})
+}
+
+// reads a digest frame, treating an empty frame as "no digest"
+async fn read_digest_frame<R: AsyncRead + Unpin>(reader: &mut R, cipher: &Aes256Gcm) -> Result<Option<[u8; 32]>, Box<dyn Error>> {
+    let digest_bytes = read_frame(reader, cipher).await?;
+    if digest_bytes.is_empty() {
+        return Ok(None);
     }
 
-    // push filebytes
-    if let Some(bytes) = &res.filebytes {
-        let filebytes_len = bytes.len() as u32;
-        result.extend_from_slice(&filebytes_len.to_be_bytes());
-        result.extend_from_slice(bytes);
+    let digest: [u8; 32] = digest_bytes.try_into()
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Malformed digest"))?;
+    Ok(Some(digest))
+}
+
+// reads a big-endian u64 frame, treating an empty frame as "none"
+async fn read_u64_frame<R: AsyncRead + Unpin>(reader: &mut R, cipher: &Aes256Gcm) -> Result<Option<u64>, Box<dyn Error>> {
+    let bytes = read_frame(reader, cipher).await?;
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+
+    let array: [u8; 8] = bytes.try_into()
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Malformed u64 field"))?;
+    Ok(Some(u64::from_be_bytes(array)))
+}
+
+// races `fut` against `duration` when given, turning an expiry into a
+// TimedOut io::Error; `None` runs `fut` with no deadline at all. Used by the
+// body-stream functions below so each chunk gets its own idle deadline,
+// rather than the whole transfer racing a single deadline - the client has
+// no idle-timeout concept of its own, so it always passes `None`.
+async fn with_idle_timeout<T>(duration: Option<Duration>, fut: impl Future<Output = Result<T, Box<dyn Error>>>) -> Result<T, Box<dyn Error>> {
+    match duration {
+        Some(d) => match timeout(d, fut).await {
+            Ok(result) => result,
+            Err(_) => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "Idle timeout waiting for body frame").into())
+        },
+        None => fut.await
     }
-    
-    Ok(result)
 }
 
-// Vec[ok, len(msg), msg, len(filename), filename, len(filebytes), filebytes] -> Response{ok, msg, filename, filebytes}
-pub async fn deserialize_response(data: &Vec<u8>) -> Result<Response, Box<dyn Error>> {
-    let mut pos = 0;
+// streams `body`'s remaining contents out as a sequence of CHUNK_SIZE
+// encrypted frames, terminated by a zero-length frame
+pub async fn write_body_stream<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(body: &mut R, writer: &mut W, cipher: &Aes256Gcm, idle_timeout: Option<Duration>) -> Result<(), Box<dyn Error>> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+
+    loop {
+        let n = with_idle_timeout(idle_timeout, async { Ok(body.read(&mut buf).await?) }).await?;
+        if n == 0 {
+            break;
+        }
+        with_idle_timeout(idle_timeout, write_frame(writer, cipher, &buf[..n])).await?;
+    }
 
-    // read ok
-    let ok = data[pos] != 0;
-    pos += 1;
+    // zero-length frame marks the end of the body
+    with_idle_timeout(idle_timeout, write_frame(writer, cipher, &[])).await
+}
 
-    // read msg
-    let msg_len = u32::from_be_bytes(data[pos..pos+4].try_into().unwrap()) as usize;
-    pos += 4;
+// reads a body stream written by write_body_stream, writing each decrypted
+// chunk into `body` as it arrives so the whole payload never sits in memory
+pub async fn read_body_stream<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(reader: &mut R, body: &mut W, cipher: &Aes256Gcm, idle_timeout: Option<Duration>) -> Result<(), Box<dyn Error>> {
+    read_body_stream_hashed(reader, body, cipher, idle_timeout).await?;
+    Ok(())
+}
 
-    let msg = String::from_utf8(data[pos..pos+msg_len].to_vec()).map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid UTF-8 sequence"))?;
-    pos += msg_len;
+// like read_body_stream, but also returns the SHA-256 digest of the bytes
+// written, computed in the same pass so the payload is only read once
+pub async fn read_body_stream_hashed<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(reader: &mut R, body: &mut W, cipher: &Aes256Gcm, idle_timeout: Option<Duration>) -> Result<[u8; 32], Box<dyn Error>> {
+    let mut hasher = Sha256::new();
 
-    // stop if pos has reached end of vec
-    // this occurs in response to read requests
-    if pos >= data.len() {
-        return Ok(Response{ok, msg, filename: None, filebytes: None});
+    loop {
+        let chunk = with_idle_timeout(idle_timeout, read_frame(reader, cipher)).await?;
+        if chunk.is_empty() {
+            break;
+        }
+        hasher.update(&chunk);
+        with_idle_timeout(idle_timeout, async { Ok(body.write_all(&chunk).await?) }).await?;
     }
 
-    // read len(filename)
-    let filename_len = u32::from_be_bytes(data[pos..pos+4].try_into().unwrap()) as usize;
-    pos += 4;
-    
-    // read filename
-    let filename = String::from_utf8(data[pos..pos+filename_len].to_vec()).map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid UTF-8 sequence"))?;
-    pos += filename_len;
+    Ok(hasher.finalize().into())
+}
+
+// computes the SHA-256 digest of `reader`'s remaining contents without
+// consuming a destination; used to checksum a file before streaming it
+pub async fn hash_reader<R: AsyncRead + Unpin>(reader: &mut R) -> Result<[u8; 32], Box<dyn Error>> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; CHUNK_SIZE];
 
-    // read number of bytes of file
-    let filebytes_len = u32::from_be_bytes(data[pos..pos+4].try_into().unwrap()) as usize;
-    pos += 4;
-    
-    // read file bytes
-    let filebytes = data[pos..pos+filebytes_len].to_vec();
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
 
-    Ok(Response{ok, msg, filename: Some(filename), filebytes: Some(filebytes)})
+    Ok(hasher.finalize().into())
 }